@@ -0,0 +1,422 @@
+//! A small round-robin database of disk usage history, so a future
+//! notification can describe a trend ("dropped 15% in the last hour")
+//! instead of just the instantaneous value.
+//!
+//! Every sample goes into the in-memory ring buffers and is appended to a
+//! journal file on disk, which is cheap to append to, so a crash doesn't
+//! lose recent samples. The consolidated ring buffers are only rewritten
+//! to disk every [`History::flush_every`] cycles, since that's a full
+//! file rewrite and too expensive to do on every tick.
+
+use serde::{Deserialize, Serialize};
+use slog::{debug, error};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MINUTE_PERIOD_SECS: u64 = 60;
+const HOUR_PERIOD_SECS: u64 = 60 * 60;
+const DAY_PERIOD_SECS: u64 = 24 * 60 * 60;
+
+const MINUTE_SAMPLES: usize = 60;
+const HOUR_SAMPLES: usize = 24;
+const DAY_SAMPLES: usize = 30;
+
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A fixed-size ring buffer of gauge samples, oldest overwritten first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RingBuffer {
+    capacity: usize,
+    samples: Vec<f64>,
+    next: usize,
+}
+
+impl RingBuffer {
+    fn new(capacity: usize) -> Self {
+        RingBuffer {
+            capacity,
+            samples: Vec::with_capacity(capacity),
+            next: 0,
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        if self.samples.len() < self.capacity {
+            self.samples.push(value);
+        } else {
+            self.samples[self.next] = value;
+        }
+        self.next = (self.next + 1) % self.capacity.max(1);
+    }
+
+    fn oldest(&self) -> Option<f64> {
+        self.samples
+            .get(self.next)
+            .or_else(|| self.samples.first())
+            .copied()
+    }
+
+    fn latest(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let index = (self.next + self.capacity - 1) % self.capacity;
+        self.samples.get(index).copied()
+    }
+}
+
+/// A single resolution of a mount's history, e.g. "one sample per hour".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Resolution {
+    period_secs: u64,
+    buffer: RingBuffer,
+    last_bucket: Option<u64>,
+}
+
+impl Resolution {
+    fn new(period_secs: u64, samples: usize) -> Self {
+        Resolution {
+            period_secs,
+            buffer: RingBuffer::new(samples),
+            last_bucket: None,
+        }
+    }
+
+    /// Records `value` unless a sample for the current period was already
+    /// recorded.
+    fn record(&mut self, timestamp: u64, value: f64) {
+        let bucket = timestamp / self.period_secs;
+        if self.last_bucket.map_or(true, |last| bucket > last) {
+            self.buffer.push(value);
+            self.last_bucket = Some(bucket);
+        }
+    }
+
+    /// How much `value` has dropped since the oldest sample still held at
+    /// this resolution, or `None` if there isn't a full window yet.
+    fn drop_since_oldest(&self) -> Option<f64> {
+        if self.buffer.samples.len() < self.buffer.capacity {
+            return None;
+        }
+        let oldest = self.buffer.oldest()?;
+        let latest = self.buffer.latest()?;
+        Some(oldest - latest)
+    }
+}
+
+/// Per-mount free-space history at minute/hour/day resolutions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MountHistory {
+    minute: Resolution,
+    hour: Resolution,
+    day: Resolution,
+}
+
+impl MountHistory {
+    fn new() -> Self {
+        MountHistory {
+            minute: Resolution::new(MINUTE_PERIOD_SECS, MINUTE_SAMPLES),
+            hour: Resolution::new(HOUR_PERIOD_SECS, HOUR_SAMPLES),
+            day: Resolution::new(DAY_PERIOD_SECS, DAY_SAMPLES),
+        }
+    }
+
+    fn record(&mut self, timestamp: u64, percentage: f64) {
+        self.minute.record(timestamp, percentage);
+        self.hour.record(timestamp, percentage);
+        self.day.record(timestamp, percentage);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct RoundRobinStore {
+    mounts: HashMap<String, MountHistory>,
+}
+
+impl RoundRobinStore {
+    fn record(&mut self, mount_point: &str, timestamp: u64, percentage: f64) {
+        self.mounts
+            .entry(mount_point.to_owned())
+            .or_insert_with(MountHistory::new)
+            .record(timestamp, percentage);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    timestamp: u64,
+    mount_point: String,
+    percentage: f64,
+}
+
+/// Rolling usage history, backed by a consolidated round-robin store file
+/// and a journal file that absorbs the cycles between consolidations.
+pub struct History {
+    log: slog::Logger,
+    store: RoundRobinStore,
+    store_path: PathBuf,
+    journal_path: PathBuf,
+    journal_file: Option<File>,
+    cycles_since_flush: usize,
+    flush_every: usize,
+}
+
+impl History {
+    /// Loads the consolidated store from `dir`, replaying any journal
+    /// entries left over from an unclean shutdown on top of it.
+    pub fn load(log: &slog::Logger, dir: &Path, flush_every: usize) -> Self {
+        let store_path = dir.join("disk-usage-history.json");
+        let journal_path = dir.join("disk-usage-history.journal");
+
+        let mut store = read_store(&store_path).unwrap_or_else(|err| {
+            debug!(log, "Starting with an empty usage history: {:#?}", err);
+            RoundRobinStore::default()
+        });
+
+        match read_journal(&journal_path) {
+            Ok(entries) => {
+                for entry in entries {
+                    store.record(&entry.mount_point, entry.timestamp, entry.percentage);
+                }
+            }
+            Err(err) => debug!(log, "No usage history journal to replay: {:#?}", err),
+        }
+
+        History {
+            log: log.clone(),
+            store,
+            store_path,
+            journal_path,
+            journal_file: None,
+            cycles_since_flush: 0,
+            flush_every,
+        }
+    }
+
+    /// Records a sample for `mount_point`, appending it to the on-disk
+    /// journal. Call [`History::end_cycle`] once per main loop tick (not per
+    /// mount) so the ring buffers are written to disk, and the journal
+    /// cleared, every `flush_every` cycles.
+    pub fn record(&mut self, mount_point: &str, percentage: f64) {
+        let timestamp = now_unix();
+        let entry = JournalEntry {
+            timestamp,
+            mount_point: mount_point.to_owned(),
+            percentage,
+        };
+        self.store
+            .record(&entry.mount_point, entry.timestamp, entry.percentage);
+        if let Err(err) = self.append_journal(&entry) {
+            error!(
+                self.log,
+                "Unable to append to usage history journal: {:#?}", err
+            );
+        }
+    }
+
+    /// Marks one main loop tick as complete, flushing the consolidated store
+    /// once `flush_every` ticks have accumulated. Must be called exactly
+    /// once per tick, regardless of how many mounts were recorded during it.
+    pub fn end_cycle(&mut self) {
+        self.cycles_since_flush += 1;
+        if self.cycles_since_flush >= self.flush_every {
+            self.flush();
+        }
+    }
+
+    /// How much the recorded free-space value has dropped over the last
+    /// hour for `mount_point`, if a full rolling hour of samples is
+    /// available. Backed by the `minute` resolution (60 samples at
+    /// 1/minute = a rolling 60-minute window) — the `hour` resolution
+    /// samples once per hour and so covers a rolling day, not a rolling
+    /// hour.
+    pub fn hourly_drop(&self, mount_point: &str) -> Option<f64> {
+        self.store
+            .mounts
+            .get(mount_point)?
+            .minute
+            .drop_since_oldest()
+    }
+
+    fn append_journal(&mut self, entry: &JournalEntry) -> Result<(), String> {
+        let file = match &mut self.journal_file {
+            Some(file) => file,
+            None => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.journal_path)
+                    .map_err(|err| format!("Unable to open journal file: {:#?}", err))?;
+                self.journal_file = Some(file);
+                self.journal_file.as_mut().unwrap()
+            }
+        };
+        let line = serde_json::to_string(entry)
+            .map_err(|err| format!("Unable to serialize journal entry: {:#?}", err))?;
+        writeln!(file, "{}", line)
+            .map_err(|err| format!("Unable to write journal entry: {:#?}", err))
+    }
+
+    fn flush(&mut self) {
+        match write_store(&self.store_path, &self.store) {
+            Ok(()) => {
+                self.journal_file = None;
+                if let Err(err) = fs::remove_file(&self.journal_path) {
+                    debug!(self.log, "No usage history journal to clear: {:#?}", err);
+                }
+                self.cycles_since_flush = 0;
+            }
+            Err(err) => error!(self.log, "Unable to flush usage history: {:#?}", err),
+        }
+    }
+}
+
+fn read_store(path: &Path) -> Result<RoundRobinStore, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read {}: {:#?}", path.display(), err))?;
+    serde_json::from_str(&contents)
+        .map_err(|err| format!("Unable to parse {}: {:#?}", path.display(), err))
+}
+
+fn write_store(path: &Path, store: &RoundRobinStore) -> Result<(), String> {
+    let contents = serde_json::to_string(store)
+        .map_err(|err| format!("Unable to serialize usage history: {:#?}", err))?;
+    fs::write(path, contents)
+        .map_err(|err| format!("Unable to write {}: {:#?}", path.display(), err))
+}
+
+fn read_journal(path: &Path) -> Result<Vec<JournalEntry>, String> {
+    let file =
+        File::open(path).map_err(|err| format!("Unable to open {}: {:#?}", path.display(), err))?;
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let line = line.map_err(|err| format!("Unable to read journal line: {:#?}", err))?;
+            serde_json::from_str(&line)
+                .map_err(|err| format!("Unable to parse journal line: {:#?}", err))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_overwrites_oldest() {
+        let mut buffer = RingBuffer::new(3);
+        buffer.push(1.0);
+        buffer.push(2.0);
+        buffer.push(3.0);
+        buffer.push(4.0);
+
+        assert_eq!(buffer.latest(), Some(4.0));
+        assert_eq!(buffer.oldest(), Some(2.0));
+    }
+
+    #[test]
+    fn test_resolution_only_records_once_per_period() {
+        let mut resolution = Resolution::new(60, 10);
+        resolution.record(0, 1.0);
+        resolution.record(30, 2.0);
+        resolution.record(61, 3.0);
+
+        assert_eq!(resolution.buffer.samples, vec![1.0, 3.0]);
+    }
+
+    #[test]
+    fn test_hourly_drop_uses_the_rolling_minute_window() {
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let mut store = RoundRobinStore::default();
+        // One sample per minute for a full rolling hour (MINUTE_SAMPLES = 60),
+        // decreasing from 100.0 down to 41.0.
+        for minute in 0..60u64 {
+            store.record("/", minute * MINUTE_PERIOD_SECS, 100.0 - minute as f64);
+        }
+        // A single hourly-resolution sample lands in the same bucket as all
+        // of the above, so it alone can't be what "hourly_drop" reports.
+        store.record("/", 0, 100.0);
+
+        let history = History {
+            log,
+            store,
+            store_path: PathBuf::new(),
+            journal_path: PathBuf::new(),
+            journal_file: None,
+            cycles_since_flush: 0,
+            flush_every: 6,
+        };
+
+        assert_eq!(history.hourly_drop("/"), Some(59.0));
+    }
+
+    #[test]
+    fn test_history_journal_survives_restart_before_flush() {
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let dir = std::env::temp_dir().join(format!(
+            "disk-usage-pushbullet-test-{:?}",
+            std::thread::current().id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut history = History::load(&log, &dir, 6);
+        history.record("/", 0.9);
+        assert!(!history.store.mounts.is_empty());
+        assert!(dir.join("disk-usage-history.journal").exists());
+        assert!(!dir.join("disk-usage-history.json").exists());
+
+        let reloaded = History::load(&log, &dir, 6);
+        assert!(!reloaded.store.mounts.is_empty());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_history_flushes_every_n_cycles() {
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let dir = std::env::temp_dir().join(format!(
+            "disk-usage-pushbullet-test-flush-{:?}",
+            std::thread::current().id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut history = History::load(&log, &dir, 2);
+        history.record("/", 0.9);
+        history.end_cycle();
+        history.record("/", 0.8);
+        history.end_cycle();
+
+        assert!(dir.join("disk-usage-history.json").exists());
+        assert!(!dir.join("disk-usage-history.journal").exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_history_counts_one_cycle_per_end_cycle_not_per_mount() {
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let dir = std::env::temp_dir().join(format!(
+            "disk-usage-pushbullet-test-multi-mount-{:?}",
+            std::thread::current().id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut history = History::load(&log, &dir, 2);
+        history.record("/", 0.9);
+        history.record("/home", 0.8);
+        history.end_cycle();
+
+        assert!(!dir.join("disk-usage-history.json").exists());
+        assert!(dir.join("disk-usage-history.journal").exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
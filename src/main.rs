@@ -1,88 +1,494 @@
-use reqwest::StatusCode;
-use serde::Serialize;
+mod history;
+mod meter;
+mod notifier;
+
+use history::History;
+use meter::Meter;
+use notifier::{Alert, Notifier, PushbulletNotifier, WebhookNotifier};
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
 use slog::Drain;
 use slog::{debug, error, info, o};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
 use std::thread;
 use std::time::Duration;
 use systemstat::{Platform, System};
+use thiserror::Error;
+
+/// The mount point watched for filesystem events when the list of mounts
+/// can't be read.
+const ROOT_MOUNT: &str = "/";
+/// Debounce interval for the filesystem watcher.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+/// Key used to track memory usage in the same previous-percentage map as
+/// disk mounts. Safe to share since mount points always start with `/`.
+const MEMORY_KEY: &str = "memory";
+/// How many loop cycles to batch before the usage history is consolidated
+/// to disk.
+const HISTORY_FLUSH_EVERY: usize = 6;
+/// Default notification budget per mount (or [`MEMORY_KEY`]) when
+/// `PUSHBULLET_RATE_LIMIT_PER_HOUR` isn't set.
+const DEFAULT_RATE_LIMIT_PER_HOUR: u32 = 4;
 
-#[derive(Serialize, Debug)]
-struct Message {
-    body: String,
-    title: &'static str,
-    r#type: &'static str,
+/// Disk usage for a single mounted filesystem.
+#[derive(Debug, Clone, PartialEq)]
+struct MountUsage {
+    mount_point: String,
+    percentage: f64,
+    avail: u64,
 }
 
-fn push(log: &slog::Logger, percentage: f64) -> Result<(), String> {
-    info!(log, "Sending push message");
-    let token = std::env::var("PUSHBULLET_TOKEN")
-        .map_err(|err| format!("Unable to get PushBullet token: {:#?}", err))?;
+/// Restricts which mount points are monitored. An empty `include` list
+/// means "every mount"; `exclude` is applied afterwards.
+#[derive(Debug, Default, Clone)]
+struct MountFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
 
-    let message = Message {
-        body: format!("Only {:.2}% left!", percentage * 100.0),
-        title: "Low disk space",
-        r#type: "note",
-    };
+impl MountFilter {
+    fn matches(&self, mount_point: &str) -> bool {
+        if !self.include.is_empty() && !self.include.iter().any(|m| m == mount_point) {
+            return false;
+        }
+        !self.exclude.iter().any(|m| m == mount_point)
+    }
+}
 
-    let client = reqwest::blocking::Client::new();
-    let req = client
-        .post("https://api.pushbullet.com/v2/pushes")
-        .header("Access-Token", token)
-        .header("Content-Type", "application/json")
-        .body(
-            serde_json::to_string(&message)
-                .map_err(|err| format!("Unable to serialize message: {:#?}", err))?,
-        );
-    let res = req
-        .send()
-        .map_err(|err| format!("Unable to send push message: {:#?}", err))?;
-    match res.status() {
-        StatusCode::OK => {
-            info!(log, "Successfully sent push message");
-            Ok(())
+/// A low disk space limit, either an absolute number of free bytes or a
+/// percentage of the filesystem's total size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Threshold {
+    Number(u64),
+    Percentage(f64),
+}
+
+#[derive(Debug, Error)]
+enum ParseThresholdError {
+    #[error("invalid percentage: {0}")]
+    InvalidPercentage(#[from] std::num::ParseFloatError),
+    #[error("percentage must be between 0.0 and 100.0, got {0}")]
+    PercentageOutOfRange(f64),
+    #[error("invalid byte count: {0}")]
+    InvalidNumber(#[from] std::num::ParseIntError),
+}
+
+impl Threshold {
+    fn is_breached(&self, percentage: f64, avail: u64) -> bool {
+        match self {
+            Threshold::Percentage(limit) => percentage * 100.0 < *limit,
+            Threshold::Number(limit) => avail < *limit,
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::Number(bytes) => write!(f, "{}B", bytes),
+            Threshold::Percentage(percentage) => write!(f, "{}%", percentage),
+        }
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = ParseThresholdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(percentage) = s.strip_suffix('%') {
+            let percentage: f64 = percentage.parse()?;
+            if !(0.0..=100.0).contains(&percentage) {
+                return Err(ParseThresholdError::PercentageOutOfRange(percentage));
+            }
+            return Ok(Threshold::Percentage(percentage));
+        }
+        Ok(Threshold::Number(parse_bytes(s)?))
+    }
+}
+
+/// Parses a byte count, accepting an optional `K`/`M`/`G`/`T` (binary)
+/// suffix and an optional trailing `B`, e.g. `"5GB"` or `"512K"`.
+fn parse_bytes(s: &str) -> Result<u64, ParseThresholdError> {
+    let upper = s.to_ascii_uppercase();
+    let upper = upper.strip_suffix('B').unwrap_or(&upper);
+    for (suffix, multiplier) in [
+        ('T', 1024u64.pow(4)),
+        ('G', 1024u64.pow(3)),
+        ('M', 1024u64.pow(2)),
+        ('K', 1024u64),
+    ] {
+        if let Some(digits) = upper.strip_suffix(suffix) {
+            let count: u64 = digits.parse()?;
+            return Ok(count * multiplier);
+        }
+    }
+    upper.parse().map_err(ParseThresholdError::InvalidNumber)
+}
+
+/// Formats a byte count using binary (1024-based) `K`/`M`/`G`/`T` units,
+/// mirroring the suffixes accepted by [`parse_bytes`].
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2}{}", value, UNITS[unit])
+}
+
+fn memory_alert(percentage: f64, used: u64, total: u64, swap: Option<(u64, u64)>) -> Alert {
+    let mut body = format!(
+        "Only {:.2}% memory free ({} / {} used)",
+        percentage * 100.0,
+        format_bytes(used),
+        format_bytes(total)
+    );
+    if let Some((swap_used, swap_total)) = swap {
+        body.push_str(&format!(
+            ", swap {} / {} used",
+            format_bytes(swap_used),
+            format_bytes(swap_total)
+        ));
+    }
+    Alert {
+        title: "Low memory",
+        body,
+    }
+}
+
+/// Returns how many notifiers were successfully notified.
+fn notify_all(log: &slog::Logger, notifiers: &[Box<dyn Notifier>], alert: &Alert) -> usize {
+    notifiers
+        .iter()
+        .filter(|notifier| match notifier.notify(alert) {
+            Ok(()) => {
+                info!(log, "Successfully sent notification");
+                true
+            }
+            Err(err) => {
+                error!(log, "Got error while notifying: {:#?}", err);
+                false
+            }
+        })
+        .count()
+}
+
+/// A mount (or `MEMORY_KEY`)'s outgoing-notification budget, plus a count
+/// of alerts that were suppressed while the budget was exhausted.
+struct MeterState {
+    meter: Meter,
+    suppressed: u32,
+}
+
+impl MeterState {
+    fn new(limit: u32, window: Duration) -> Self {
+        MeterState {
+            meter: Meter::new(limit, window),
+            suppressed: 0,
+        }
+    }
+}
+
+/// Charges `key`'s meter before sending `alert`, suppressing the send and
+/// logging at `info` when the budget is exhausted. The next alert that
+/// does get through mentions how many were coalesced in the meantime.
+fn notify_rate_limited(
+    log: &slog::Logger,
+    notifiers: &[Box<dyn Notifier>],
+    meters: &mut HashMap<String, MeterState>,
+    key: &str,
+    rate_limit: u32,
+    rate_limit_window: Duration,
+    mut alert: Alert,
+) {
+    let state = meters
+        .entry(key.to_owned())
+        .or_insert_with(|| MeterState::new(rate_limit, rate_limit_window));
+
+    if !state.meter.try_consume() {
+        state.suppressed += 1;
+        info!(log, "Suppressing notification, rate limit exhausted"; "suppressed" => state.suppressed);
+        return;
+    }
+
+    if state.suppressed > 0 {
+        alert
+            .body
+            .push_str(&format!(" ({} alerts coalesced)", state.suppressed));
+        state.suppressed = 0;
+    }
+
+    if notify_all(log, notifiers, &alert) == 0 {
+        state.meter.refund();
+    }
+}
+
+/// Lists the mount points `filter` allows, so the caller can watch the same
+/// set of mounts that `disk_usage` will later report on.
+fn monitored_mount_points(log: &slog::Logger, filter: &MountFilter) -> Vec<String> {
+    match disk_usage(log, filter) {
+        Ok(usages) => usages.into_iter().map(|usage| usage.mount_point).collect(),
+        Err(err) => {
+            error!(
+                log,
+                "Unable to list mounts to watch, falling back to {}: {:#?}", ROOT_MOUNT, err
+            );
+            vec![ROOT_MOUNT.to_owned()]
         }
-        e => Err(format!("Got error from PushBullet: {:#?}", e)),
     }
 }
 
-fn disk_usage(log: &slog::Logger) -> Result<f64, String> {
+fn disk_usage(log: &slog::Logger, filter: &MountFilter) -> Result<Vec<MountUsage>, String> {
     debug!(log, "Checking disk usage");
     let sys = System::new();
-    let root_mount = sys
+    let mounts = sys
         .mounts()
-        .map_err(|err| format!("Sys mount error: {:#?}", err))
-        .and_then(|mounts| {
-            mounts
-                .into_iter()
-                .find(|mount| mount.fs_mounted_on == "/")
-                .ok_or_else(|| "Unable to find root mount".to_owned())
-        })?;
-    Ok(root_mount.avail.as_u64() as f64 / root_mount.total.as_u64() as f64)
-}
-
-fn should_push(percentage: f64, treshold: f64, previous_percentage: f64) -> bool {
-    if percentage >= treshold {
+        .map_err(|err| format!("Sys mount error: {:#?}", err))?;
+    Ok(mounts
+        .into_iter()
+        .filter(|mount| filter.matches(&mount.fs_mounted_on))
+        .map(|mount| {
+            let avail = mount.avail.as_u64();
+            MountUsage {
+                mount_point: mount.fs_mounted_on,
+                percentage: avail as f64 / mount.total.as_u64() as f64,
+                avail,
+            }
+        })
+        .collect())
+}
+
+/// Returns `(free / total fraction, free bytes, total bytes)` for system RAM.
+fn memory_usage(log: &slog::Logger) -> Result<(f64, u64, u64), String> {
+    debug!(log, "Checking memory usage");
+    let sys = System::new();
+    let memory = sys
+        .memory()
+        .map_err(|err| format!("Sys memory error: {:#?}", err))?;
+    let free = memory.free.as_u64();
+    let total = memory.total.as_u64();
+    Ok((free as f64 / total as f64, free, total))
+}
+
+/// Returns `(used bytes, total bytes)` for swap.
+fn swap_usage(log: &slog::Logger) -> Result<(u64, u64), String> {
+    debug!(log, "Checking swap usage");
+    let sys = System::new();
+    let swap = sys
+        .swap()
+        .map_err(|err| format!("Sys swap error: {:#?}", err))?;
+    let total = swap.total.as_u64();
+    Ok((total - swap.free.as_u64(), total))
+}
+
+/// Free-space reading carried between loop iterations, so `should_push` can
+/// re-run its anti-spam check in whichever unit the configured [`Threshold`]
+/// is actually expressed in.
+#[derive(Debug, Clone, Copy)]
+struct UsageState {
+    percentage: f64,
+    avail: u64,
+}
+
+impl Default for UsageState {
+    /// Before the first reading, treat the disk as entirely free so the very first
+    /// breach always notifies.
+    fn default() -> Self {
+        UsageState {
+            percentage: 1.0,
+            avail: u64::MAX,
+        }
+    }
+}
+
+/// How far `avail` must drop since `previous` before a `Threshold::Number`
+/// breach is worth re-notifying about.
+const ABSOLUTE_RENOTIFY_STEP: u64 = 1024 * 1024 * 1024;
+
+fn should_push(percentage: f64, avail: u64, treshold: &Threshold, previous: UsageState) -> bool {
+    if !treshold.is_breached(percentage, avail) {
         return false;
     }
     // If treshold is met we want to avoid spamming unless disk usage keeps increasing
-    (percentage * 100.0).floor() < (previous_percentage * 100.0).floor()
+    match treshold {
+        Threshold::Percentage(_) => {
+            (percentage * 100.0).floor() < (previous.percentage * 100.0).floor()
+        }
+        Threshold::Number(_) => avail + ABSOLUTE_RENOTIFY_STEP <= previous.avail,
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn check_disk_usage(
     log: &slog::Logger,
-    percentage: f64,
-    treshold: f64,
-    previous_percentage: f64,
-) -> Result<f64, String> {
-    if percentage < treshold {
-        info!(log, "Low disk space treshold met");
+    usages: Vec<MountUsage>,
+    treshold: &Threshold,
+    mut previous_usage: HashMap<String, UsageState>,
+    history: &mut History,
+    notifiers: &[Box<dyn Notifier>],
+    meters: &mut HashMap<String, MeterState>,
+    rate_limit: u32,
+    rate_limit_window: Duration,
+) -> HashMap<String, UsageState> {
+    for usage in usages {
+        let log = log.new(o!(
+            "mount" => usage.mount_point.clone(),
+            "percentage" => format!("{:.2}", usage.percentage),
+        ));
+        let previous = previous_usage
+            .get(&usage.mount_point)
+            .copied()
+            .unwrap_or_default();
+        history.record(&usage.mount_point, usage.percentage);
+        if treshold.is_breached(usage.percentage, usage.avail) {
+            info!(log, "Low disk space treshold met");
+        } else {
+            debug!(log, "Low disk space treshold not met");
+        }
+        if should_push(usage.percentage, usage.avail, treshold, previous) {
+            let mut body = format!(
+                "Only {:.2}% left on {}",
+                usage.percentage * 100.0,
+                usage.mount_point
+            );
+            if let Some(drop) = history.hourly_drop(&usage.mount_point) {
+                body.push_str(&format!(", dropped {:.2}% in the last hour", drop * 100.0));
+            }
+            let alert = Alert {
+                title: "Low disk space",
+                body,
+            };
+            notify_rate_limited(
+                &log,
+                notifiers,
+                meters,
+                &usage.mount_point,
+                rate_limit,
+                rate_limit_window,
+                alert,
+            );
+        }
+        previous_usage.insert(
+            usage.mount_point,
+            UsageState {
+                percentage: usage.percentage,
+                avail: usage.avail,
+            },
+        );
+    }
+    history.end_cycle();
+    previous_usage
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_memory_usage(
+    log: &slog::Logger,
+    treshold: &Threshold,
+    mut previous_usage: HashMap<String, UsageState>,
+    notifiers: &[Box<dyn Notifier>],
+    meters: &mut HashMap<String, MeterState>,
+    rate_limit: u32,
+    rate_limit_window: Duration,
+) -> HashMap<String, UsageState> {
+    let (percentage, free, total) = match memory_usage(log) {
+        Ok(usage) => usage,
+        Err(err) => {
+            error!(log, "Got error while calculating memory usage: {:#?}", err);
+            return previous_usage;
+        }
+    };
+    let log = log.new(o!("percentage" => format!("{:.2}", percentage)));
+    let previous = previous_usage.get(MEMORY_KEY).copied().unwrap_or_default();
+    if treshold.is_breached(percentage, free) {
+        info!(log, "Low memory treshold met");
     } else {
-        debug!(log, "Low disk space treshold not met");
+        debug!(log, "Low memory treshold not met");
     }
-    if should_push(percentage, treshold, previous_percentage) {
-        push(&log, percentage)?;
+    if should_push(percentage, free, treshold, previous) {
+        let swap = match swap_usage(&log) {
+            Ok(swap) => Some(swap),
+            Err(err) => {
+                error!(log, "Got error while calculating swap usage: {:#?}", err);
+                None
+            }
+        };
+        let alert = memory_alert(percentage, total - free, total, swap);
+        notify_rate_limited(
+            &log,
+            notifiers,
+            meters,
+            MEMORY_KEY,
+            rate_limit,
+            rate_limit_window,
+            alert,
+        );
     }
-    Ok(percentage)
+    previous_usage.insert(
+        MEMORY_KEY.to_owned(),
+        UsageState {
+            percentage,
+            avail: free,
+        },
+    );
+    previous_usage
+}
+
+/// Spawns a thread that watches every mount point in `paths` for filesystem
+/// events and reports them on the returned channel, so the main loop can
+/// react to a sudden fill-up on any monitored mount instead of waiting for
+/// the next periodic poll. Each mount point directory is watched
+/// non-recursively, so changes deeper in its tree are not reported and are
+/// only picked up by the periodic poll.
+fn watch_for_events(log: &slog::Logger, paths: Vec<String>) -> Receiver<()> {
+    let (events_tx, events_rx) = channel();
+    let log = log.clone();
+    thread::spawn(move || {
+        let (watcher_tx, watcher_rx) = channel();
+        let mut watcher = match watcher(watcher_tx, WATCH_DEBOUNCE) {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                error!(log, "Unable to create filesystem watcher: {:#?}", err);
+                return;
+            }
+        };
+        for path in &paths {
+            if let Err(err) = watcher.watch(path, RecursiveMode::NonRecursive) {
+                error!(log, "Unable to watch {}: {:#?}", path, err);
+            }
+        }
+        for event in watcher_rx {
+            if let DebouncedEvent::Error(err, _) = &event {
+                error!(log, "Filesystem watcher error: {:#?}", err);
+                continue;
+            }
+            debug!(log, "Filesystem event: {:#?}", event);
+            if events_tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
+    events_rx
+}
+
+/// Reads a comma-separated list of mount points from an env var, e.g.
+/// `PUSHBULLET_INCLUDE_MOUNTS=/,/home`.
+fn mount_list_from_env(var: &str) -> Vec<String> {
+    std::env::var(var)
+        .ok()
+        .map(|value| {
+            value
+                .split(',')
+                .map(|mount| mount.trim().to_owned())
+                .filter(|mount| !mount.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 fn main() {
@@ -90,45 +496,116 @@ fn main() {
     let drain = slog_term::FullFormat::new(decorator).build().fuse();
     let drain = slog_async::Async::new(drain).build().fuse();
     let root = slog::Logger::root(drain, o!());
-    let treshold = 0.1;
+    let treshold = std::env::var("PUSHBULLET_THRESHOLD")
+        .ok()
+        .and_then(|value| match value.parse() {
+            Ok(treshold) => Some(treshold),
+            Err(err) => {
+                error!(root, "Invalid PUSHBULLET_THRESHOLD: {:#?}", err);
+                None
+            }
+        })
+        .unwrap_or(Threshold::Percentage(10.0));
+    let memory_treshold = std::env::var("PUSHBULLET_MEMORY_THRESHOLD")
+        .ok()
+        .and_then(|value| match value.parse() {
+            Ok(treshold) => Some(treshold),
+            Err(err) => {
+                error!(root, "Invalid PUSHBULLET_MEMORY_THRESHOLD: {:#?}", err);
+                None
+            }
+        })
+        .unwrap_or(Threshold::Percentage(10.0));
+    let filter = MountFilter {
+        include: mount_list_from_env("PUSHBULLET_INCLUDE_MOUNTS"),
+        exclude: mount_list_from_env("PUSHBULLET_EXCLUDE_MOUNTS"),
+    };
     let sleep_time = Duration::from_secs(60 * 5);
+    let state_dir = std::env::var("PUSHBULLET_STATE_DIR").unwrap_or_else(|_| ".".to_owned());
+    let rate_limit: u32 = std::env::var("PUSHBULLET_RATE_LIMIT_PER_HOUR")
+        .ok()
+        .and_then(|value| match value.parse() {
+            Ok(rate_limit) => Some(rate_limit),
+            Err(err) => {
+                error!(root, "Invalid PUSHBULLET_RATE_LIMIT_PER_HOUR: {:#?}", err);
+                None
+            }
+        })
+        .unwrap_or(DEFAULT_RATE_LIMIT_PER_HOUR);
+    let rate_limit_window = Duration::from_secs(60 * 60);
+
+    let mut notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(PushbulletNotifier)];
+    if let Ok(url) = std::env::var("PUSHBULLET_WEBHOOK_URL") {
+        notifiers.push(Box::new(WebhookNotifier::new(url)));
+    }
 
-    let mut previous_percentage = 1.0;
+    let mut previous_usage: HashMap<String, UsageState> = HashMap::new();
+    let mut meters: HashMap<String, MeterState> = HashMap::new();
 
     info!(root, "Starting disk-warn");
-    let log = root.new(o!("treshold" => treshold, "sleep_time" => sleep_time.as_secs()));
+    let log =
+        root.new(o!("treshold" => format!("{}", treshold), "sleep_time" => sleep_time.as_secs()));
+    let events = watch_for_events(&log, monitored_mount_points(&log, &filter));
+    let mut history = History::load(&log, Path::new(&state_dir), HISTORY_FLUSH_EVERY);
     loop {
-        let log = log.new(o!("previous" => format!("{:.2}", previous_percentage)));
-        let percentage = match disk_usage(&log) {
-            Ok(percentage) => percentage,
+        let usages = match disk_usage(&log, &filter) {
+            Ok(usages) => usages,
             Err(err) => {
                 error!(log, "Got error while calculating disk usage: {:#?}", err);
+                thread::sleep(sleep_time);
                 continue;
             }
         };
-        let log = log.new(o!("percentage" => format!("{:.2}", percentage)));
-        match check_disk_usage(&log, percentage, treshold, previous_percentage) {
-            Ok(percentage) => {
-                previous_percentage = percentage;
-            }
-            Err(err) => {
-                error!(log, "Got error while checking disk usage: {:#?}", err);
+        previous_usage = check_disk_usage(
+            &log,
+            usages,
+            &treshold,
+            previous_usage,
+            &mut history,
+            &notifiers,
+            &mut meters,
+            rate_limit,
+            rate_limit_window,
+        );
+        previous_usage = check_memory_usage(
+            &log,
+            &memory_treshold,
+            previous_usage,
+            &notifiers,
+            &mut meters,
+            rate_limit,
+            rate_limit_window,
+        );
+        // Wait for a filesystem event on any monitored mount, falling back
+        // to the periodic poll interval if nothing happens in the meantime.
+        match events.recv_timeout(sleep_time) {
+            Ok(()) => debug!(log, "Woken by filesystem event"),
+            Err(RecvTimeoutError::Timeout) => debug!(log, "Woken by periodic poll"),
+            Err(RecvTimeoutError::Disconnected) => {
+                error!(
+                    log,
+                    "Filesystem watcher stopped, falling back to periodic poll only"
+                );
+                thread::sleep(sleep_time);
             }
         }
-        thread::sleep(sleep_time);
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+
     #[test]
     fn test_should_push_initial_below_treshold() {
         let percentage = 0.09;
-        let previous = 1.0;
-        let treshold = 0.1;
+        let previous = UsageState {
+            percentage: 1.0,
+            avail: 0,
+        };
+        let treshold = Threshold::Percentage(10.0);
 
-        let push = should_push(percentage, treshold, previous);
+        let push = should_push(percentage, 0, &treshold, previous);
 
         assert!(
             push,
@@ -139,10 +616,13 @@ mod test {
     #[test]
     fn test_should_not_push_initial_above_treshold() {
         let percentage = 0.20;
-        let previous = 0.3;
-        let treshold = 0.1;
+        let previous = UsageState {
+            percentage: 0.3,
+            avail: 0,
+        };
+        let treshold = Threshold::Percentage(10.0);
 
-        let push = should_push(percentage, treshold, previous);
+        let push = should_push(percentage, 0, &treshold, previous);
 
         assert!(
             !push,
@@ -153,10 +633,13 @@ mod test {
     #[test]
     fn test_should_not_push_unchanged() {
         let percentage = 0.09;
-        let previous = 0.09;
-        let treshold = 0.1;
+        let previous = UsageState {
+            percentage: 0.09,
+            avail: 0,
+        };
+        let treshold = Threshold::Percentage(10.0);
 
-        let push = should_push(percentage, treshold, previous);
+        let push = should_push(percentage, 0, &treshold, previous);
 
         assert!(!push, "Push should be false when there is no change");
     }
@@ -164,10 +647,13 @@ mod test {
     #[test]
     fn test_should_not_push_less_than_one_percent() {
         let percentage = 0.09;
-        let previous = 0.09;
-        let treshold = 0.1;
+        let previous = UsageState {
+            percentage: 0.09,
+            avail: 0,
+        };
+        let treshold = Threshold::Percentage(10.0);
 
-        let push = should_push(percentage, treshold, previous);
+        let push = should_push(percentage, 0, &treshold, previous);
 
         assert!(
             !push,
@@ -178,10 +664,13 @@ mod test {
     #[test]
     fn test_should_push_more_than_one_percent() {
         let percentage = 0.08;
-        let previous = 0.09;
-        let treshold = 0.1;
+        let previous = UsageState {
+            percentage: 0.09,
+            avail: 0,
+        };
+        let treshold = Threshold::Percentage(10.0);
 
-        let push = should_push(percentage, treshold, previous);
+        let push = should_push(percentage, 0, &treshold, previous);
 
         assert!(
             push,
@@ -192,10 +681,13 @@ mod test {
     #[test]
     fn test_should_not_push_increasing() {
         let percentage = 0.09;
-        let previous = 0.08;
-        let treshold = 0.1;
+        let previous = UsageState {
+            percentage: 0.08,
+            avail: 0,
+        };
+        let treshold = Threshold::Percentage(10.0);
 
-        let push = should_push(percentage, treshold, previous);
+        let push = should_push(percentage, 0, &treshold, previous);
 
         assert!(
             !push,
@@ -206,14 +698,221 @@ mod test {
     #[test]
     fn test_should_not_push_increase_above_treshold() {
         let percentage = 0.11;
-        let previous = 0.09;
-        let treshold = 0.1;
+        let previous = UsageState {
+            percentage: 0.09,
+            avail: 0,
+        };
+        let treshold = Threshold::Percentage(10.0);
 
-        let push = should_push(percentage, treshold, previous);
+        let push = should_push(percentage, 0, &treshold, previous);
 
         assert!(
             !push,
             "Push should be false when less than one percent has changed"
         );
     }
+
+    #[test]
+    fn test_should_not_push_absolute_threshold_small_drop() {
+        let treshold = Threshold::Number(10 * 1024u64.pow(3));
+        let avail = 5 * 1024u64.pow(3);
+        let previous = UsageState {
+            percentage: 0.5,
+            avail: avail + 1024,
+        };
+
+        let push = should_push(0.5, avail, &treshold, previous);
+
+        assert!(
+            !push,
+            "Push should be false when avail hasn't dropped by a full step since the previous reading"
+        );
+    }
+
+    #[test]
+    fn test_should_push_absolute_threshold_full_step_drop() {
+        let treshold = Threshold::Number(10 * 1024u64.pow(3));
+        let avail = 5 * 1024u64.pow(3);
+        let previous = UsageState {
+            percentage: 0.5,
+            avail: avail + ABSOLUTE_RENOTIFY_STEP,
+        };
+
+        let push = should_push(0.5, avail, &treshold, previous);
+
+        assert!(
+            push,
+            "Push should be true once avail has dropped by a full step since the previous reading"
+        );
+    }
+
+    #[test]
+    fn test_parse_threshold_percentage() {
+        let treshold: Threshold = "10%".parse().unwrap();
+
+        assert_eq!(treshold, Threshold::Percentage(10.0));
+    }
+
+    #[test]
+    fn test_parse_threshold_number_with_suffix() {
+        let treshold: Threshold = "5GB".parse().unwrap();
+
+        assert_eq!(treshold, Threshold::Number(5 * 1024u64.pow(3)));
+    }
+
+    #[test]
+    fn test_parse_threshold_number_plain() {
+        let treshold: Threshold = "1024".parse().unwrap();
+
+        assert_eq!(treshold, Threshold::Number(1024));
+    }
+
+    #[test]
+    fn test_parse_threshold_percentage_out_of_range() {
+        let result: Result<Threshold, _> = "150%".parse();
+
+        assert!(matches!(
+            result,
+            Err(ParseThresholdError::PercentageOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_breach_number_threshold_uses_avail_bytes() {
+        let treshold = Threshold::Number(1024);
+
+        assert!(treshold.is_breached(0.99, 512));
+        assert!(!treshold.is_breached(0.01, 2048));
+    }
+
+    #[test]
+    fn test_mount_filter_defaults_to_everything() {
+        let filter = MountFilter::default();
+
+        assert!(filter.matches("/"));
+        assert!(filter.matches("/home"));
+    }
+
+    #[test]
+    fn test_mount_filter_include_restricts_to_listed_mounts() {
+        let filter = MountFilter {
+            include: vec!["/home".to_owned()],
+            exclude: vec![],
+        };
+
+        assert!(!filter.matches("/"));
+        assert!(filter.matches("/home"));
+    }
+
+    #[test]
+    fn test_mount_filter_exclude_removes_listed_mounts() {
+        let filter = MountFilter {
+            include: vec![],
+            exclude: vec!["/boot".to_owned()],
+        };
+
+        assert!(filter.matches("/"));
+        assert!(!filter.matches("/boot"));
+    }
+
+    #[test]
+    fn test_check_disk_usage_tracks_percentage_per_mount() {
+        let log = slog::Logger::root(slog::Discard, o!());
+        let treshold = Threshold::Percentage(10.0);
+        let usages = vec![
+            MountUsage {
+                mount_point: "/".to_owned(),
+                percentage: 0.5,
+                avail: 500,
+            },
+            MountUsage {
+                mount_point: "/home".to_owned(),
+                percentage: 0.2,
+                avail: 200,
+            },
+        ];
+
+        let dir = std::env::temp_dir().join(format!(
+            "disk-usage-pushbullet-test-main-{:?}",
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut history = History::load(&log, &dir, 6);
+
+        let notifiers: Vec<Box<dyn Notifier>> = vec![];
+        let mut meters: HashMap<String, MeterState> = HashMap::new();
+        let previous = check_disk_usage(
+            &log,
+            usages,
+            &treshold,
+            HashMap::new(),
+            &mut history,
+            &notifiers,
+            &mut meters,
+            DEFAULT_RATE_LIMIT_PER_HOUR,
+            Duration::from_secs(3600),
+        );
+
+        assert_eq!(previous.get("/").map(|state| state.percentage), Some(0.5));
+        assert_eq!(
+            previous.get("/home").map(|state| state.percentage),
+            Some(0.2)
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    struct AlwaysSucceedsNotifier;
+
+    impl Notifier for AlwaysSucceedsNotifier {
+        fn notify(&self, _event: &Alert) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_check_disk_usage_suppresses_past_rate_limit() {
+        let log = slog::Logger::root(slog::Discard, o!());
+        let treshold = Threshold::Percentage(10.0);
+        let dir = std::env::temp_dir().join(format!(
+            "disk-usage-pushbullet-test-rate-limit-{:?}",
+            std::thread::current().id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut history = History::load(&log, &dir, 6);
+        let notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(AlwaysSucceedsNotifier)];
+        let mut meters: HashMap<String, MeterState> = HashMap::new();
+        let mut previous = HashMap::new();
+
+        for _ in 0..3 {
+            let usages = vec![MountUsage {
+                mount_point: "/".to_owned(),
+                percentage: 0.05,
+                avail: 50,
+            }];
+            previous = check_disk_usage(
+                &log,
+                usages,
+                &treshold,
+                previous,
+                &mut history,
+                &notifiers,
+                &mut meters,
+                1,
+                Duration::from_secs(3600),
+            );
+            previous.remove("/");
+        }
+
+        assert_eq!(meters.get("/").unwrap().suppressed, 2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512.00B");
+        assert_eq!(format_bytes(1024), "1.00K");
+        assert_eq!(format_bytes(5 * 1024u64.pow(3)), "5.00G");
+    }
 }
@@ -0,0 +1,118 @@
+//! A token-bucket style rate limiter, so a thrashing workload that keeps
+//! crossing the threshold can't flood every configured notifier.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Caps how many units can be consumed within a rolling time window.
+pub struct Meter {
+    limit: u32,
+    window: Duration,
+    usage: VecDeque<Instant>,
+}
+
+impl Meter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Meter {
+            limit,
+            window,
+            usage: VecDeque::new(),
+        }
+    }
+
+    fn evict_expired(&mut self, now: Instant) {
+        while let Some(&oldest) = self.usage.front() {
+            if now.duration_since(oldest) >= self.window {
+                self.usage.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Attempts to charge one unit against the budget. Returns `false`
+    /// without charging anything if the limit has already been reached.
+    pub fn try_consume(&mut self) -> bool {
+        let now = Instant::now();
+        self.evict_expired(now);
+        if self.usage.len() >= self.limit as usize {
+            return false;
+        }
+        self.usage.push_back(now);
+        true
+    }
+
+    /// Gives back the most recently consumed unit, e.g. when the send it
+    /// was charged for ultimately failed and shouldn't count against the
+    /// budget.
+    pub fn refund(&mut self) {
+        self.usage.pop_back();
+    }
+
+    /// Unconditionally records a unit of usage, bypassing the limit check
+    /// in `try_consume`. Useful for reconstructing a meter's state from
+    /// units that were already sent through some other path.
+    pub fn record(&mut self) {
+        self.usage.push_back(Instant::now());
+    }
+
+    /// How many units can still be consumed in the current window.
+    pub fn remaining(&mut self) -> u32 {
+        self.evict_expired(Instant::now());
+        self.limit.saturating_sub(self.usage.len() as u32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_try_consume_respects_limit() {
+        let mut meter = Meter::new(2, Duration::from_secs(3600));
+
+        assert!(meter.try_consume());
+        assert!(meter.try_consume());
+        assert!(!meter.try_consume());
+    }
+
+    #[test]
+    fn test_refund_gives_back_a_unit() {
+        let mut meter = Meter::new(1, Duration::from_secs(3600));
+
+        assert!(meter.try_consume());
+        assert!(!meter.try_consume());
+
+        meter.refund();
+
+        assert!(meter.try_consume());
+    }
+
+    #[test]
+    fn test_record_counts_against_the_limit() {
+        let mut meter = Meter::new(1, Duration::from_secs(3600));
+
+        meter.record();
+
+        assert!(!meter.try_consume());
+    }
+
+    #[test]
+    fn test_remaining_reflects_current_usage() {
+        let mut meter = Meter::new(3, Duration::from_secs(3600));
+
+        meter.try_consume();
+
+        assert_eq!(meter.remaining(), 2);
+    }
+
+    #[test]
+    fn test_usage_expires_after_window() {
+        let mut meter = Meter::new(1, Duration::from_millis(10));
+
+        assert!(meter.try_consume());
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(meter.try_consume());
+    }
+}
@@ -0,0 +1,116 @@
+//! Notification backends. `check_disk_usage`/`check_memory_usage` fan an
+//! [`Alert`] out to every configured [`Notifier`], so a breach can reach
+//! more than just PushBullet.
+
+use reqwest::StatusCode;
+use serde::Serialize;
+
+/// A notification to deliver to every configured backend.
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub title: &'static str,
+    pub body: String,
+}
+
+pub trait Notifier {
+    fn notify(&self, event: &Alert) -> Result<(), String>;
+}
+
+#[derive(Serialize, Debug)]
+struct PushbulletMessage<'a> {
+    body: &'a str,
+    title: &'a str,
+    r#type: &'static str,
+}
+
+/// Sends alerts as PushBullet "note" pushes, authenticated with the
+/// `PUSHBULLET_TOKEN` env var.
+pub struct PushbulletNotifier;
+
+impl Notifier for PushbulletNotifier {
+    fn notify(&self, event: &Alert) -> Result<(), String> {
+        let token = std::env::var("PUSHBULLET_TOKEN")
+            .map_err(|err| format!("Unable to get PushBullet token: {:#?}", err))?;
+
+        let message = PushbulletMessage {
+            body: &event.body,
+            title: event.title,
+            r#type: "note",
+        };
+
+        let client = reqwest::blocking::Client::new();
+        let req = client
+            .post("https://api.pushbullet.com/v2/pushes")
+            .header("Access-Token", token)
+            .header("Content-Type", "application/json")
+            .body(
+                serde_json::to_string(&message)
+                    .map_err(|err| format!("Unable to serialize message: {:#?}", err))?,
+            );
+        let res = req
+            .send()
+            .map_err(|err| format!("Unable to send push message: {:#?}", err))?;
+        match res.status() {
+            StatusCode::OK => Ok(()),
+            e => Err(format!("Got error from PushBullet: {:#?}", e)),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct WebhookPayload<'a> {
+    title: &'a str,
+    body: &'a str,
+}
+
+/// Sends alerts as a JSON POST to an arbitrary webhook URL.
+pub struct WebhookNotifier {
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        WebhookNotifier { url }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, event: &Alert) -> Result<(), String> {
+        let payload = WebhookPayload {
+            title: event.title,
+            body: &event.body,
+        };
+
+        let client = reqwest::blocking::Client::new();
+        let res = client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(
+                serde_json::to_string(&payload)
+                    .map_err(|err| format!("Unable to serialize payload: {:#?}", err))?,
+            )
+            .send()
+            .map_err(|err| format!("Unable to send webhook request: {:#?}", err))?;
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Got error from webhook: {:#?}", res.status()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_webhook_notifier_fails_without_reachable_url() {
+        let notifier = WebhookNotifier::new("http://127.0.0.1:0".to_owned());
+        let alert = Alert {
+            title: "Low disk space",
+            body: "Only 1.00% left on /".to_owned(),
+        };
+
+        assert!(notifier.notify(&alert).is_err());
+    }
+}